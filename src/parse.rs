@@ -3,12 +3,85 @@ pub(crate) enum Quantifier {
     ExactlyOne,
     ZeroOrOne,
     ZeroOrMore,
+    Range { min: usize, max: Option<usize> },
 }
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum PosixClass {
+    Alpha,
+    Digit,
+    Space,
+    Upper,
+    Lower,
+    Alnum,
+    Punct,
+    Cntrl,
+    Print,
+    Graph,
+    Blank,
+    Xdigit,
+}
+
+impl PosixClass {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alpha" => Some(Self::Alpha),
+            "digit" => Some(Self::Digit),
+            "space" => Some(Self::Space),
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "alnum" => Some(Self::Alnum),
+            "punct" => Some(Self::Punct),
+            "cntrl" => Some(Self::Cntrl),
+            "print" => Some(Self::Print),
+            "graph" => Some(Self::Graph),
+            "blank" => Some(Self::Blank),
+            "xdigit" => Some(Self::Xdigit),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn contains(&self, c: char) -> bool {
+        match self {
+            Self::Alpha => c.is_alphabetic(),
+            Self::Digit => c.is_ascii_digit(),
+            Self::Space => c.is_whitespace(),
+            Self::Upper => c.is_uppercase(),
+            Self::Lower => c.is_lowercase(),
+            Self::Alnum => c.is_alphanumeric(),
+            Self::Punct => c.is_ascii_punctuation(),
+            Self::Cntrl => c.is_control(),
+            Self::Print => !c.is_control(),
+            Self::Graph => !c.is_control() && !c.is_whitespace(),
+            Self::Blank => c == ' ' || c == '\t',
+            Self::Xdigit => c.is_ascii_hexdigit(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum AnchorKind {
+    Start,
+    End,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum MatcherKind {
     Wildcard,
     Element(char),
-    Group(Vec<Matcher>),
+    Group { index: usize, items: Vec<Matcher> },
+    // `group_index` is `Some` when this alternation is itself the direct
+    // content of a `(...)` (e.g. `(a|b)`), so its matched branch counts as
+    // that group's capture; a bare top-level `a|b` has no capture to report.
+    Alternation {
+        group_index: Option<usize>,
+        branches: Vec<Vec<Matcher>>,
+    },
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+        posix: Vec<PosixClass>,
+    },
+    Anchor(AnchorKind),
 }
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct Matcher {
@@ -29,19 +102,171 @@ impl Matcher {
             matcher_kind: MatcherKind::Element(c),
         }
     }
-    pub(crate) fn group(items: Vec<Matcher>, q: Quantifier) -> Self {
+    pub(crate) fn group(index: usize, items: Vec<Matcher>, q: Quantifier) -> Self {
+        Self {
+            quantifier: q,
+            matcher_kind: MatcherKind::Group { index, items },
+        }
+    }
+    pub(crate) fn alternation(
+        group_index: Option<usize>,
+        branches: Vec<Vec<Matcher>>,
+        q: Quantifier,
+    ) -> Self {
         Self {
             quantifier: q,
-            matcher_kind: MatcherKind::Group(items),
+            matcher_kind: MatcherKind::Alternation {
+                group_index,
+                branches,
+            },
+        }
+    }
+    pub(crate) fn class(
+        negated: bool,
+        ranges: Vec<(char, char)>,
+        posix: Vec<PosixClass>,
+        q: Quantifier,
+    ) -> Self {
+        Self {
+            quantifier: q,
+            matcher_kind: MatcherKind::Class {
+                negated,
+                ranges,
+                posix,
+            },
+        }
+    }
+    pub(crate) fn anchor(kind: AnchorKind, q: Quantifier) -> Self {
+        Self {
+            quantifier: q,
+            matcher_kind: MatcherKind::Anchor(kind),
         }
     }
 }
 
+fn parse_bracket(chars: &[char], start: usize) -> Result<(Matcher, usize), String> {
+    let mut i = start + 1;
+    let mut negated = false;
+    if chars.get(i) == Some(&'^') {
+        negated = true;
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut posix = Vec::new();
+    let mut at_start = true;
+
+    loop {
+        match chars.get(i) {
+            None => return Err(format!("Unterminated character class at index {}", start)),
+            Some(']') if !at_start => {
+                i += 1;
+                break;
+            }
+            Some('[') if chars.get(i + 1) == Some(&':') => {
+                let name_start = i + 2;
+                let mut j = name_start;
+                while chars.get(j).is_some_and(|c| *c != ':') {
+                    j += 1;
+                }
+                if chars.get(j) != Some(&':') || chars.get(j + 1) != Some(&']') {
+                    return Err(format!("Malformed POSIX class at index {}", i));
+                }
+                let name: String = chars[name_start..j].iter().collect();
+                let class = PosixClass::from_name(&name)
+                    .ok_or_else(|| format!("Unknown POSIX class [:{}:]", name))?;
+                posix.push(class);
+                i = j + 2;
+            }
+            Some(&lo) => {
+                i += 1;
+                // `-` is only a range operator when it has both a preceding and a
+                // following char; at the ends of the class it is a literal `-`.
+                if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|c| *c != ']') {
+                    let hi = chars[i + 1];
+                    ranges.push((lo, hi));
+                    i += 2;
+                } else {
+                    ranges.push((lo, lo));
+                }
+            }
+        }
+        at_start = false;
+    }
+
+    Ok((
+        Matcher::class(negated, ranges, posix, Quantifier::ExactlyOne),
+        i,
+    ))
+}
+
+fn parse_repetition_range(chars: &[char], start: usize) -> Result<(Quantifier, usize), String> {
+    fn parse_number(chars: &[char], at: usize) -> Option<(usize, usize)> {
+        let mut end = at;
+        while chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+            end += 1;
+        }
+        if end == at {
+            return None;
+        }
+        let n: usize = chars[at..end].iter().collect::<String>().parse().ok()?;
+        Some((n, end))
+    }
+
+    let (min, mut i) = parse_number(chars, start + 1)
+        .ok_or_else(|| format!("Expected a number at index {}", start + 1))?;
+
+    match chars.get(i) {
+        Some('}') => Ok((Quantifier::Range { min, max: Some(min) }, i + 1)),
+        Some(',') => {
+            i += 1;
+            match parse_number(chars, i) {
+                Some((max, after_max)) => {
+                    i = after_max;
+                    if chars.get(i) != Some(&'}') {
+                        return Err(format!("Expected '}}' at index {}", i));
+                    }
+                    if max < min {
+                        return Err(format!(
+                            "Invalid repetition {{{},{}}} at index {}: max is less than min",
+                            min, max, start
+                        ));
+                    }
+                    Ok((
+                        Quantifier::Range {
+                            min,
+                            max: Some(max),
+                        },
+                        i + 1,
+                    ))
+                }
+                None => {
+                    if chars.get(i) != Some(&'}') {
+                        return Err(format!("Expected '}}' at index {}", i));
+                    }
+                    Ok((Quantifier::Range { min, max: None }, i + 1))
+                }
+            }
+        }
+        _ => Err(format!("Expected ',' or '}}' at index {}", i)),
+    }
+}
+
 pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
+    let chars: Vec<char> = re.chars().collect();
     let mut stack = vec![Vec::new()];
+    // Branches of the alternation already closed off by a `|` at each depth,
+    // parallel to `stack`; the frame on `stack` itself holds the branch in progress.
+    let mut alt_stack: Vec<Vec<Vec<Matcher>>> = vec![Vec::new()];
+    // Capture index assigned to each open `(`, in the order the parens were
+    // opened (standard left-to-right capture numbering); index 0 is reserved
+    // for the whole match, so explicit groups start at 1.
+    let mut group_index_stack: Vec<usize> = Vec::new();
+    let mut next_group_index = 1;
 
     let mut i = 0;
-    for next in re.chars() {
+    while i < chars.len() {
+        let next = chars[i];
         match next {
             '.' => {
                 stack
@@ -51,32 +276,69 @@ pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
                 i += 1;
             }
             '\\' => {
-                if i + 1 >= re.len() {
+                if i + 1 >= chars.len() {
                     return Err(format!("Bad escape character at index {}", i));
                 }
-                stack.last_mut().unwrap().push(Matcher::element(
-                    re.chars().nth(i + 1).unwrap(),
-                    Quantifier::ExactlyOne,
-                ));
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .push(Matcher::element(chars[i + 1], Quantifier::ExactlyOne));
                 i += 2;
             }
+            '[' => {
+                let (matcher, next_i) = parse_bracket(&chars, i)?;
+                stack.last_mut().unwrap().push(matcher);
+                i = next_i;
+            }
+            '^' if i == 0 => {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .push(Matcher::anchor(AnchorKind::Start, Quantifier::ExactlyOne));
+                i += 1;
+            }
+            '$' if i == chars.len() - 1 => {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .push(Matcher::anchor(AnchorKind::End, Quantifier::ExactlyOne));
+                i += 1;
+            }
             '(' => {
                 stack.push(Vec::new());
+                alt_stack.push(Vec::new());
+                group_index_stack.push(next_group_index);
+                next_group_index += 1;
                 i += 1;
             }
             ')' => {
                 if stack.len() <= 1 {
                     return Err(format!("No group to close at index {}", i));
                 }
-                let states = stack.pop().unwrap();
-                stack
-                    .last_mut()
-                    .unwrap()
-                    .push(Matcher::group(states, Quantifier::ExactlyOne));
+                let last_branch = stack.pop().unwrap();
+                let mut branches = alt_stack.pop().unwrap();
+                let index = group_index_stack.pop().unwrap();
+                let matcher = if branches.is_empty() {
+                    Matcher::group(index, last_branch, Quantifier::ExactlyOne)
+                } else {
+                    branches.push(last_branch);
+                    Matcher::alternation(Some(index), branches, Quantifier::ExactlyOne)
+                };
+                stack.last_mut().unwrap().push(matcher);
+                i += 1;
+            }
+            '|' => {
+                let current_branch = std::mem::take(stack.last_mut().unwrap());
+                alt_stack.last_mut().unwrap().push(current_branch);
                 i += 1;
             }
             '?' => {
-                let mut last_elem = stack.last_mut().unwrap().last_mut().unwrap();
+                let Some(last_elem) = stack.last_mut().unwrap().last_mut() else {
+                    return Err(format!(
+                        "Quantifier must follow an unqualified element or group at index {}",
+                        i
+                    ));
+                };
                 if last_elem.quantifier != Quantifier::ExactlyOne {
                     return Err(
                         "Quantifier must follow an unqualified element or group".to_string()
@@ -86,7 +348,12 @@ pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
                 i += 1;
             }
             '*' => {
-                let mut last_elem = stack.last_mut().unwrap().last_mut().unwrap();
+                let Some(last_elem) = stack.last_mut().unwrap().last_mut() else {
+                    return Err(format!(
+                        "Quantifier must follow an unqualified element or group at index {}",
+                        i
+                    ));
+                };
                 if last_elem.quantifier != Quantifier::ExactlyOne {
                     return Err(
                         "Quantifier must follow an unqualified element or group".to_string()
@@ -96,7 +363,12 @@ pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
                 i += 1;
             }
             '+' => {
-                let last_elem = stack.last_mut().unwrap().last_mut().unwrap();
+                let Some(last_elem) = stack.last_mut().unwrap().last_mut() else {
+                    return Err(format!(
+                        "Quantifier must follow an unqualified element or group at index {}",
+                        i
+                    ));
+                };
                 if last_elem.quantifier != Quantifier::ExactlyOne {
                     return Err(
                         "Quantifier must follow an unqualified element or group".to_string()
@@ -108,6 +380,22 @@ pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
                 stack.last_mut().unwrap().push(zero_or_more_copy);
                 i += 1;
             }
+            '{' => {
+                let Some(last_elem) = stack.last_mut().unwrap().last_mut() else {
+                    return Err(format!(
+                        "Quantifier must follow an unqualified element or group at index {}",
+                        i
+                    ));
+                };
+                if last_elem.quantifier != Quantifier::ExactlyOne {
+                    return Err(
+                        "Quantifier must follow an unqualified element or group".to_string()
+                    );
+                }
+                let (quantifier, next_i) = parse_repetition_range(&chars, i)?;
+                last_elem.quantifier = quantifier;
+                i = next_i;
+            }
 
             _ => {
                 stack
@@ -122,7 +410,43 @@ pub(crate) fn parse_re(re: &str) -> Result<Vec<Matcher>, String> {
     if stack.len() != 1 {
         return Err("Unmatched groups in regular expression".to_string());
     }
-    Ok(stack.pop().unwrap())
+    let last_branch = stack.pop().unwrap();
+    let mut branches = alt_stack.pop().unwrap();
+    if branches.is_empty() {
+        Ok(last_branch)
+    } else {
+        branches.push(last_branch);
+        Ok(vec![Matcher::alternation(
+            None,
+            branches,
+            Quantifier::ExactlyOne,
+        )])
+    }
+}
+
+pub(crate) fn class_matches(negated: bool, ranges: &[(char, char)], posix: &[PosixClass], c: char) -> bool {
+    let is_member = ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi)
+        || posix.iter().any(|p| p.contains(c));
+    is_member != negated
+}
+
+pub(crate) fn count_groups(items: &[Matcher]) -> usize {
+    items.iter().map(count_groups_in_matcher).max().unwrap_or(0)
+}
+
+fn count_groups_in_matcher(matcher: &Matcher) -> usize {
+    match &matcher.matcher_kind {
+        MatcherKind::Group { index, items } => (*index).max(count_groups(items)),
+        MatcherKind::Alternation {
+            group_index,
+            branches,
+        } => {
+            let own = group_index.unwrap_or(0);
+            let nested = branches.iter().map(|b| count_groups(b)).max().unwrap_or(0);
+            own.max(nested)
+        }
+        _ => 0,
+    }
 }
 
 #[cfg(test)]
@@ -160,6 +484,7 @@ mod tests {
             ]),
             parse_re("ab?c")
         );
+        assert!(parse_re("?a").is_err());
     }
     #[test]
     fn zero_or_more() {
@@ -171,6 +496,7 @@ mod tests {
             ]),
             parse_re("ab*c")
         );
+        assert!(parse_re("*a").is_err());
     }
     #[test]
     fn one_or_more() {
@@ -183,6 +509,7 @@ mod tests {
             ]),
             parse_re("ab+c")
         );
+        assert!(parse_re("+a").is_err());
     }
 
     #[test]
@@ -190,7 +517,7 @@ mod tests {
         assert_eq!(
             Ok(vec![
                 Matcher::element('a', Quantifier::ExactlyOne),
-                Matcher::group(Vec::new(), Quantifier::ExactlyOne)
+                Matcher::group(1, Vec::new(), Quantifier::ExactlyOne)
             ]),
             parse_re("a()")
         );
@@ -198,6 +525,7 @@ mod tests {
             Ok(vec![
                 Matcher::element('a', Quantifier::ExactlyOne),
                 Matcher::group(
+                    1,
                     vec![
                         Matcher::element('b', Quantifier::ExactlyOne),
                         Matcher::element('c', Quantifier::ExactlyOne),
@@ -209,4 +537,189 @@ mod tests {
             parse_re("a(bc)?d")
         );
     }
+
+    #[test]
+    fn nested_group_indices() {
+        // Capture indices are assigned in the order the opening `(` is seen,
+        // left to right, regardless of nesting depth.
+        assert_eq!(
+            Ok(vec![Matcher::group(
+                1,
+                vec![
+                    Matcher::element('a', Quantifier::ExactlyOne),
+                    Matcher::group(
+                        2,
+                        vec![Matcher::element('b', Quantifier::ExactlyOne)],
+                        Quantifier::ExactlyOne
+                    ),
+                    Matcher::element('c', Quantifier::ExactlyOne),
+                ],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("(a(b)c)")
+        );
+        assert_eq!(2, count_groups(&parse_re("(a(b)c)").unwrap()));
+    }
+
+    #[test]
+    fn alternation() {
+        assert_eq!(
+            Ok(vec![Matcher::alternation(
+                None,
+                vec![
+                    vec![
+                        Matcher::element('f', Quantifier::ExactlyOne),
+                        Matcher::element('o', Quantifier::ExactlyOne),
+                        Matcher::element('o', Quantifier::ExactlyOne),
+                    ],
+                    vec![
+                        Matcher::element('b', Quantifier::ExactlyOne),
+                        Matcher::element('a', Quantifier::ExactlyOne),
+                        Matcher::element('r', Quantifier::ExactlyOne),
+                    ],
+                ],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("foo|bar")
+        );
+        assert_eq!(
+            Ok(vec![
+                Matcher::element('a', Quantifier::ExactlyOne),
+                Matcher::alternation(
+                    Some(1),
+                    vec![
+                        vec![Matcher::element('b', Quantifier::ExactlyOne)],
+                        vec![
+                            Matcher::element('c', Quantifier::ExactlyOne),
+                            Matcher::element('d', Quantifier::ExactlyOne),
+                        ],
+                    ],
+                    Quantifier::ExactlyOne
+                ),
+                Matcher::element('e', Quantifier::ExactlyOne),
+            ]),
+            parse_re("a(b|cd)e")
+        );
+    }
+
+    #[test]
+    fn bracket_class() {
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                false,
+                vec![('a', 'a'), ('b', 'b'), ('c', 'c')],
+                vec![],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[abc]")
+        );
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                false,
+                vec![('a', 'z'), ('0', '9')],
+                vec![],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[a-z0-9]")
+        );
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                true,
+                vec![('a', 'c')],
+                vec![],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[^a-c]")
+        );
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                false,
+                vec![],
+                vec![PosixClass::Alpha, PosixClass::Digit],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[[:alpha:][:digit:]]")
+        );
+        // `]` right after `[` (or `[^`) is a literal, not the terminator.
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                false,
+                vec![(']', ']'), ('a', 'a')],
+                vec![],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[]a]")
+        );
+        // `-` at the start or end of the class is a literal, not a range.
+        assert_eq!(
+            Ok(vec![Matcher::class(
+                false,
+                vec![('-', '-'), ('a', 'a')],
+                vec![],
+                Quantifier::ExactlyOne
+            )]),
+            parse_re("[-a]")
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(
+            Ok(vec![
+                Matcher::anchor(AnchorKind::Start, Quantifier::ExactlyOne),
+                Matcher::element('a', Quantifier::ExactlyOne),
+            ]),
+            parse_re("^a")
+        );
+        assert_eq!(
+            Ok(vec![
+                Matcher::element('a', Quantifier::ExactlyOne),
+                Matcher::anchor(AnchorKind::End, Quantifier::ExactlyOne),
+            ]),
+            parse_re("a$")
+        );
+        assert_eq!(
+            Ok(vec![
+                Matcher::anchor(AnchorKind::Start, Quantifier::ExactlyOne),
+                Matcher::element('a', Quantifier::ExactlyOne),
+                Matcher::anchor(AnchorKind::End, Quantifier::ExactlyOne),
+            ]),
+            parse_re("^a$")
+        );
+    }
+
+    #[test]
+    fn repetition_range() {
+        assert_eq!(
+            Ok(vec![Matcher::element(
+                'a',
+                Quantifier::Range {
+                    min: 2,
+                    max: Some(2)
+                }
+            )]),
+            parse_re("a{2}")
+        );
+        assert_eq!(
+            Ok(vec![Matcher::element(
+                'a',
+                Quantifier::Range { min: 2, max: None }
+            )]),
+            parse_re("a{2,}")
+        );
+        assert_eq!(
+            Ok(vec![Matcher::element(
+                'a',
+                Quantifier::Range {
+                    min: 2,
+                    max: Some(5)
+                }
+            )]),
+            parse_re("a{2,5}")
+        );
+        assert!(parse_re("a{}").is_err());
+        assert!(parse_re("a{2").is_err());
+        assert!(parse_re("a{3,1}").is_err());
+        assert!(parse_re("{2}").is_err());
+    }
 }
@@ -0,0 +1,371 @@
+use crate::parse::{class_matches, count_groups, parse_re, AnchorKind, Matcher, MatcherKind, PosixClass, Quantifier};
+
+#[derive(Debug, Clone)]
+enum Op {
+    Char(char),
+    Any,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+        posix: Vec<PosixClass>,
+    },
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    AssertStart,
+    AssertEnd,
+    Match,
+}
+
+fn shift(prog: &mut [Op], by: usize) {
+    for op in prog.iter_mut() {
+        match op {
+            Op::Split(a, b) => {
+                *a += by;
+                *b += by;
+            }
+            Op::Jmp(a) => *a += by,
+            _ => {}
+        }
+    }
+}
+
+fn compile_zero_or_one(mut body: Vec<Op>) -> Vec<Op> {
+    let mut prog = Vec::with_capacity(body.len() + 1);
+    prog.push(Op::Split(1, body.len() + 1));
+    shift(&mut body, 1);
+    prog.extend(body);
+    prog
+}
+
+fn compile_zero_or_more(mut body: Vec<Op>) -> Vec<Op> {
+    let mut prog = Vec::with_capacity(body.len() + 2);
+    prog.push(Op::Split(1, body.len() + 2));
+    shift(&mut body, 1);
+    prog.extend(body);
+    prog.push(Op::Jmp(0));
+    prog
+}
+
+fn compile_range(body: Vec<Op>, min: usize, max: Option<usize>) -> Vec<Op> {
+    let mut prog = Vec::new();
+    for _ in 0..min {
+        let mut rep = body.clone();
+        shift(&mut rep, prog.len());
+        prog.extend(rep);
+    }
+    match max {
+        Some(max) => {
+            for _ in 0..(max - min) {
+                let mut opt = compile_zero_or_one(body.clone());
+                shift(&mut opt, prog.len());
+                prog.extend(opt);
+            }
+        }
+        None => {
+            let mut star = compile_zero_or_more(body.clone());
+            shift(&mut star, prog.len());
+            prog.extend(star);
+        }
+    }
+    prog
+}
+
+fn assemble_alternation(mut branch_progs: Vec<Vec<Op>>) -> Vec<Op> {
+    if branch_progs.len() == 1 {
+        return branch_progs.pop().unwrap();
+    }
+    let first = branch_progs.remove(0);
+    let rest = assemble_alternation(branch_progs);
+
+    let mut prog = Vec::new();
+    prog.push(Op::Split(1, 1 + first.len() + 1));
+    let mut first = first;
+    shift(&mut first, 1);
+    prog.extend(first);
+    let jmp_idx = prog.len();
+    prog.push(Op::Jmp(0)); // patched below once `rest`'s length is known
+    let mut rest = rest;
+    shift(&mut rest, prog.len());
+    prog.extend(rest);
+    prog[jmp_idx] = Op::Jmp(prog.len());
+    prog
+}
+
+fn compile_seq(items: &[Matcher]) -> Vec<Op> {
+    let mut prog = Vec::new();
+    for m in items {
+        let mut atom = compile_quantified(m);
+        shift(&mut atom, prog.len());
+        prog.extend(atom);
+    }
+    prog
+}
+
+fn compile_quantified(m: &Matcher) -> Vec<Op> {
+    let body = compile_atom(&m.matcher_kind);
+    match &m.quantifier {
+        Quantifier::ExactlyOne => body,
+        Quantifier::ZeroOrOne => compile_zero_or_one(body),
+        Quantifier::ZeroOrMore => compile_zero_or_more(body),
+        Quantifier::Range { min, max } => compile_range(body, *min, *max),
+    }
+}
+
+fn compile_atom(kind: &MatcherKind) -> Vec<Op> {
+    match kind {
+        MatcherKind::Wildcard => vec![Op::Any],
+        MatcherKind::Element(c) => vec![Op::Char(*c)],
+        MatcherKind::Class {
+            negated,
+            ranges,
+            posix,
+        } => vec![Op::Class {
+            negated: *negated,
+            ranges: ranges.clone(),
+            posix: posix.clone(),
+        }],
+        MatcherKind::Anchor(AnchorKind::Start) => vec![Op::AssertStart],
+        MatcherKind::Anchor(AnchorKind::End) => vec![Op::AssertEnd],
+        MatcherKind::Group { index, items } => {
+            let mut body = compile_seq(items);
+            shift(&mut body, 1);
+            let mut prog = vec![Op::Save(2 * index)];
+            prog.extend(body);
+            prog.push(Op::Save(2 * index + 1));
+            prog
+        }
+        MatcherKind::Alternation {
+            group_index,
+            branches,
+        } => {
+            let branch_progs = branches.iter().map(|b| compile_seq(b)).collect();
+            let inner = assemble_alternation(branch_progs);
+            match group_index {
+                Some(idx) => {
+                    let mut inner = inner;
+                    shift(&mut inner, 1);
+                    let mut prog = vec![Op::Save(2 * idx)];
+                    prog.extend(inner);
+                    prog.push(Op::Save(2 * idx + 1));
+                    prog
+                }
+                None => inner,
+            }
+        }
+    }
+}
+
+fn compile(items: &[Matcher]) -> Vec<Op> {
+    let mut body = compile_seq(items);
+    shift(&mut body, 1);
+    let mut prog = vec![Op::Save(0)];
+    prog.extend(body);
+    prog.push(Op::Save(1));
+    prog.push(Op::Match);
+    prog
+}
+
+type Saves = Vec<Option<usize>>;
+
+type Captures = Vec<Option<(usize, usize)>>;
+
+struct Thread {
+    pc: usize,
+    saves: Saves,
+}
+
+// `visited` deduplicates by `pc` within a single step so thread count stays
+// bounded by the program's size no matter how much backtracking-prone
+// structure it has.
+fn add_thread(
+    prog: &[Op],
+    list: &mut Vec<Thread>,
+    visited: &mut [bool],
+    pc: usize,
+    saves: Saves,
+    i: usize,
+    len: usize,
+) {
+    if visited[pc] {
+        return;
+    }
+    visited[pc] = true;
+    match &prog[pc] {
+        Op::Split(a, b) => {
+            add_thread(prog, list, visited, *a, saves.clone(), i, len);
+            add_thread(prog, list, visited, *b, saves, i, len);
+        }
+        Op::Jmp(a) => add_thread(prog, list, visited, *a, saves, i, len),
+        Op::Save(slot) => {
+            let mut saves = saves;
+            if *slot >= saves.len() {
+                saves.resize(slot + 1, None);
+            }
+            saves[*slot] = Some(i);
+            add_thread(prog, list, visited, pc + 1, saves, i, len);
+        }
+        Op::AssertStart => {
+            if i == 0 {
+                add_thread(prog, list, visited, pc + 1, saves, i, len);
+            }
+        }
+        Op::AssertEnd => {
+            if i == len {
+                add_thread(prog, list, visited, pc + 1, saves, i, len);
+            }
+        }
+        Op::Char(_) | Op::Any | Op::Class { .. } | Op::Match => {
+            list.push(Thread { pc, saves });
+        }
+    }
+}
+
+fn run(prog: &[Op], s: &[char]) -> Option<Saves> {
+    let len = s.len();
+    let mut clist = Vec::new();
+    let mut visited = vec![false; prog.len()];
+    add_thread(prog, &mut clist, &mut visited, 0, Vec::new(), 0, len);
+
+    let mut best: Option<Saves> = None;
+    let mut i = 0;
+    loop {
+        if clist.is_empty() {
+            break;
+        }
+        let mut nlist = Vec::new();
+        let mut nvisited = vec![false; prog.len()];
+        for thread in clist {
+            match &prog[thread.pc] {
+                Op::Char(c) => {
+                    if i < len && s[i] == *c {
+                        add_thread(prog, &mut nlist, &mut nvisited, thread.pc + 1, thread.saves, i + 1, len);
+                    }
+                }
+                Op::Any => {
+                    if i < len {
+                        add_thread(prog, &mut nlist, &mut nvisited, thread.pc + 1, thread.saves, i + 1, len);
+                    }
+                }
+                Op::Class {
+                    negated,
+                    ranges,
+                    posix,
+                } => {
+                    if i < len && class_matches(*negated, ranges, posix, s[i]) {
+                        add_thread(prog, &mut nlist, &mut nvisited, thread.pc + 1, thread.saves, i + 1, len);
+                    }
+                }
+                Op::Match => {
+                    // Lower-priority threads queued after this one in
+                    // `clist` lose to it; higher-priority threads already
+                    // folded into `nlist` this step are still live and may
+                    // yet produce a preferred (greedier) match later.
+                    best = Some(thread.saves);
+                    break;
+                }
+                _ => unreachable!("add_thread only ever queues consuming ops or Match"),
+            }
+        }
+        if i >= len {
+            break;
+        }
+        clist = nlist;
+        i += 1;
+    }
+    best
+}
+
+pub fn test_re_linear(re: &str, s: &str) -> Result<Option<usize>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let matchers = parse_re(re)?;
+    let prog = compile(&matchers);
+    Ok(run(&prog, &chars).map(|saves| saves[1].unwrap()))
+}
+
+pub fn captures_linear(re: &str, s: &str) -> Result<Option<Captures>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let matchers = parse_re(re)?;
+    let group_count = count_groups(&matchers);
+    let prog = compile(&matchers);
+    let Some(saves) = run(&prog, &chars) else {
+        return Ok(None);
+    };
+    let mut captures = vec![Some((saves[0].unwrap(), saves[1].unwrap()))];
+    for idx in 1..=group_count {
+        let span = match (saves.get(2 * idx).copied().flatten(), saves.get(2 * idx + 1).copied().flatten()) {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None,
+        };
+        captures.push(span);
+    }
+    Ok(Some(captures))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic() {
+        assert_eq!(Ok(Some(1)), test_re_linear(".", "a"));
+        assert_eq!(Ok(Some(3)), test_re_linear("abc", "abc"));
+        assert_eq!(Ok(None), test_re_linear("abc", "abd"));
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        assert_eq!(Ok(Some(3)), test_re_linear("ab?c", "abc"));
+        assert_eq!(Ok(Some(2)), test_re_linear("ab?c", "ac"));
+        assert_eq!(Ok(Some(10)), test_re_linear("ab*c*", "abbbbbcccc"));
+        assert_eq!(Ok(Some(2)), test_re_linear("a{2}", "aa"));
+        assert_eq!(Ok(None), test_re_linear("a{2,}", "a"));
+        assert_eq!(Ok(Some(3)), test_re_linear("a{2,3}", "aaaa"));
+    }
+
+    #[test]
+    fn test_groups_and_alternation() {
+        assert_eq!(Ok(Some(5)), test_re_linear("a(bcd)c", "abcdc"));
+        assert_eq!(Ok(Some(2)), test_re_linear("a(bcd)?c", "ac"));
+        assert_eq!(Ok(Some(3)), test_re_linear("foo|bar", "foo"));
+        assert_eq!(Ok(Some(3)), test_re_linear("a(b|cd)e", "abe"));
+        assert_eq!(Ok(Some(4)), test_re_linear("a(b|cd)e", "acde"));
+        assert_eq!(Ok(Some(3)), test_re_linear("(a|ab)c", "abc"));
+    }
+
+    #[test]
+    fn test_bracket_class_and_anchors() {
+        assert_eq!(Ok(Some(1)), test_re_linear("[a-z0-9]", "7"));
+        assert_eq!(Ok(None), test_re_linear("[^a-c]", "b"));
+        assert_eq!(Ok(Some(1)), test_re_linear("[[:alpha:]]", "x"));
+        assert_eq!(Ok(Some(3)), test_re_linear("a.*c$", "abc"));
+        assert_eq!(Ok(None), test_re_linear("a.*c$", "abcd"));
+    }
+
+    #[test]
+    fn test_catastrophic_backtracking_pattern_stays_linear() {
+        // `(a*)*c` against a long run of non-matching `a`s is exponential
+        // for a naive backtracker; the VM must still finish promptly.
+        let input = "a".repeat(100);
+        assert_eq!(Ok(None), test_re_linear("(a*)*c", &input));
+    }
+
+    #[test]
+    fn test_captures() {
+        assert_eq!(
+            Ok(Some(vec![Some((0, 3)), Some((1, 2))])),
+            captures_linear("a(b)c", "abc")
+        );
+        assert_eq!(
+            Ok(Some(vec![Some((0, 2)), None])),
+            captures_linear("a(bcd)?c", "ac")
+        );
+        assert_eq!(Ok(None), captures_linear("abc", "xyz"));
+        // Matches rx_match::captures: the outer `*` takes zero repetitions,
+        // so the group inside never participates and reports `None`.
+        assert_eq!(
+            Ok(Some(vec![Some((0, 0)), None])),
+            captures_linear("(a*)*", "b")
+        );
+    }
+}
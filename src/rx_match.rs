@@ -1,9 +1,14 @@
-use crate::parse::{parse_re, Matcher, MatcherKind, Quantifier};
+use crate::parse::{count_groups, parse_re, AnchorKind, Matcher, MatcherKind, Quantifier};
+
+type Captures = Vec<Option<(usize, usize)>>;
 
 struct BacktrackState {
     is_backtrackable: bool,
     matcher: Matcher,
     consumptions: Vec<usize>,
+    // Capture slots as they stood right before this matcher was attempted,
+    // so a backtrack past it also undoes whatever it captured.
+    captures_before: Captures,
 }
 
 struct Re {
@@ -11,13 +16,25 @@ struct Re {
     matcher_stack: Vec<Matcher>,
     current_state: Option<Matcher>,
     backtrack_stack: Vec<BacktrackState>,
+    captures: Captures,
 }
 
 fn matches_string_at_index(
     matcher: &Matcher,
     s: &[char],
     i: usize,
+    captures: &mut Captures,
 ) -> Result<(bool, usize), String> {
+    // Anchors consume no characters and are meaningful even at the end of the
+    // input, so they must be checked before the length guard below.
+    if let MatcherKind::Anchor(kind) = &matcher.matcher_kind {
+        let at_anchor = match kind {
+            AnchorKind::Start => i == 0,
+            AnchorKind::End => i == s.len(),
+        };
+        return Ok((at_anchor, 0));
+    }
+
     if i >= s.len() {
         return Ok((false, 0));
     }
@@ -33,17 +50,63 @@ fn matches_string_at_index(
                 return Ok((false, 0));
             }
         }
-        MatcherKind::Group(items) => return Re::new(items).test_internal(&s[i..]),
+        MatcherKind::Group { index, items } => {
+            let mut nested = Re::new(items, captures.len());
+            let (is_match, consumed) = nested.test_internal(&s[i..])?;
+            if is_match {
+                merge_captures(captures, &nested.captures);
+                captures[*index] = Some((i, i + consumed));
+            }
+            return Ok((is_match, consumed));
+        }
+        MatcherKind::Alternation {
+            group_index,
+            branches,
+        } => {
+            for branch in branches {
+                let mut nested = Re::new(branch, captures.len());
+                let (is_match, consumed) = nested.test_internal(&s[i..])?;
+                if is_match {
+                    merge_captures(captures, &nested.captures);
+                    if let Some(idx) = group_index {
+                        captures[*idx] = Some((i, i + consumed));
+                    }
+                    return Ok((true, consumed));
+                }
+            }
+            return Ok((false, 0));
+        }
+        MatcherKind::Class {
+            negated,
+            ranges,
+            posix,
+        } => {
+            if crate::parse::class_matches(*negated, ranges, posix, s[i]) {
+                return Ok((true, 1));
+            } else {
+                return Ok((false, 0));
+            }
+        }
+        MatcherKind::Anchor(_) => unreachable!("anchors are handled above the length guard"),
+    }
+}
+
+fn merge_captures(into: &mut Captures, from: &Captures) {
+    for (slot, span) in from.iter().enumerate() {
+        if span.is_some() {
+            into[slot] = *span;
+        }
     }
 }
 
 impl Re {
-    fn new(states: &Vec<Matcher>) -> Self {
+    fn new(states: &Vec<Matcher>, group_count: usize) -> Self {
         Self {
             i: 0,
             matcher_stack: (states).into_iter().rev().map(|it| it.clone()).collect(),
             backtrack_stack: Vec::new(),
             current_state: None,
+            captures: vec![None; group_count],
         }
     }
 
@@ -56,19 +119,54 @@ impl Re {
                 is_backtrackable,
                 matcher,
                 mut consumptions,
+                captures_before,
             } = self.backtrack_stack.pop().unwrap();
+            self.captures = captures_before;
 
             if is_backtrackable {
-                if consumptions.len() == 0 {
+                // A `Range` only gives back repetitions above its `min` floor;
+                // at the floor it is drained and its mandatory ones are undone.
+                let floor = match matcher.quantifier {
+                    Quantifier::Range { min, .. } => min,
+                    _ => 0,
+                };
+                if consumptions.len() <= floor {
+                    // An untried alternation branch has nothing to undo; retrying
+                    // `matcher` itself *is* the backtrack step. A drained
+                    // `ZeroOrMore`/`ZeroOrOne` can also land here with
+                    // `is_backtrackable` still true but has no alternative left to
+                    // offer, so only alternation gets to retry.
+                    if matches!(matcher.matcher_kind, MatcherKind::Alternation { .. }) {
+                        self.matcher_stack.push(matcher);
+                        could_backtrack = true;
+                        break;
+                    }
                     self.matcher_stack.push(matcher);
+                    for n in consumptions {
+                        self.i -= n;
+                    }
                     continue;
                 } else {
                     let n = consumptions.pop().unwrap();
                     self.i -= n;
+                    // An alternation with no repetitions left to give back has
+                    // nothing more to undo; retrying `matcher` itself *is* the
+                    // backtrack step, so it must go straight back onto
+                    // `matcher_stack`, not wait behind another failure to get
+                    // there via the `consumptions.len() == 0` branch above.
+                    if consumptions.is_empty()
+                        && matches!(matcher.matcher_kind, MatcherKind::Alternation { .. })
+                    {
+                        self.matcher_stack.push(matcher);
+                        could_backtrack = true;
+                        break;
+                    }
+                    let captures_before = self.captures.clone();
                     self.backtrack_stack.push(BacktrackState {
                         is_backtrackable,
                         matcher,
                         consumptions,
+                        captures_before,
                     });
                     could_backtrack = true;
                     break;
@@ -93,7 +191,65 @@ impl Re {
             let st = self.current_state.as_ref().unwrap();
             match st.quantifier {
                 Quantifier::ExactlyOne => {
-                    let (is_match, consumed) = matches_string_at_index(&st, s, self.i)?;
+                    if let MatcherKind::Alternation {
+                        group_index,
+                        branches,
+                    } = &st.matcher_kind
+                    {
+                        let group_index = *group_index;
+                        let captures_before = self.captures.clone();
+                        let mut matched = None;
+                        for (idx, branch) in branches.iter().enumerate() {
+                            let mut nested = Re::new(branch, self.captures.len());
+                            let (is_match, consumed) = nested.test_internal(&s[self.i..])?;
+                            if is_match {
+                                merge_captures(&mut self.captures, &nested.captures);
+                                matched = Some((idx, consumed));
+                                break;
+                            }
+                        }
+
+                        if matched.is_none() {
+                            let index_before_backtracking = self.i;
+                            let could_backtrack = self.backtrack();
+                            if !could_backtrack {
+                                return Ok((false, index_before_backtracking));
+                            }
+                            continue;
+                        }
+                        let (idx, consumed) = matched.unwrap();
+                        if let Some(gi) = group_index {
+                            self.captures[gi] = Some((self.i, self.i + consumed));
+                        }
+
+                        let untried = &branches[idx + 1..];
+                        if untried.is_empty() {
+                            self.backtrack_stack.push(BacktrackState {
+                                is_backtrackable: false,
+                                matcher: self.current_state.clone().unwrap(), // another bad `clone`
+                                consumptions: vec![consumed],
+                                captures_before,
+                            });
+                        } else {
+                            self.backtrack_stack.push(BacktrackState {
+                                is_backtrackable: true,
+                                matcher: Matcher::alternation(
+                                    group_index,
+                                    untried.to_vec(),
+                                    Quantifier::ExactlyOne,
+                                ),
+                                consumptions: vec![consumed],
+                                captures_before,
+                            });
+                        }
+                        self.i += consumed;
+                        self.current_state = self.matcher_stack.pop();
+                        continue;
+                    }
+
+                    let captures_before = self.captures.clone();
+                    let (is_match, consumed) =
+                        matches_string_at_index(&st, s, self.i, &mut self.captures)?;
                     if !is_match {
                         let index_before_backtracking = self.i;
                         let could_backtrack = self.backtrack();
@@ -106,26 +262,31 @@ impl Re {
                         is_backtrackable: false,
                         matcher: self.current_state.clone().unwrap(), // another bad `clone`
                         consumptions: vec![consumed],
+                        captures_before,
                     });
                     self.i += consumed;
                     self.current_state = self.matcher_stack.pop();
                 }
                 Quantifier::ZeroOrOne => {
+                    let captures_before = self.captures.clone();
                     if self.i >= s.len() {
                         self.backtrack_stack.push(BacktrackState {
                             is_backtrackable: false,
                             matcher: self.current_state.clone().unwrap(), // another bad `clone`
                             consumptions: vec![0],
+                            captures_before,
                         });
                         self.current_state = self.matcher_stack.pop();
                         continue;
                     }
-                    let (is_match, consumed) = matches_string_at_index(&st, s, self.i)?;
+                    let (is_match, consumed) =
+                        matches_string_at_index(&st, s, self.i, &mut self.captures)?;
                     self.i += consumed;
                     self.backtrack_stack.push(BacktrackState {
                         is_backtrackable: is_match && consumed > 0,
                         matcher: self.current_state.clone().unwrap(), // another bad `clone`
                         consumptions: vec![consumed],
+                        captures_before,
                     });
                     self.current_state = self.matcher_stack.pop();
                     continue;
@@ -135,6 +296,7 @@ impl Re {
                         is_backtrackable: true,
                         matcher: self.current_state.clone().unwrap(),
                         consumptions: Vec::new(),
+                        captures_before: self.captures.clone(),
                     };
                     loop {
                         if self.i >= s.len() {
@@ -146,8 +308,14 @@ impl Re {
                             self.current_state = self.matcher_stack.pop();
                             break;
                         }
-                        let (is_match, consumed) = matches_string_at_index(&st, s, self.i)?;
+                        let captures_before_rep = self.captures.clone();
+                        let (is_match, consumed) =
+                            matches_string_at_index(&st, s, self.i, &mut self.captures)?;
                         if !is_match || consumed == 0 {
+                            // A zero-width match doesn't count as a repetition
+                            // (taking it would loop forever), so it must not
+                            // leave behind whatever it captured either.
+                            self.captures = captures_before_rep;
                             if backtrack_state.consumptions.len() == 0 {
                                 backtrack_state.is_backtrackable = false;
                                 backtrack_state.consumptions.push(0);
@@ -160,6 +328,50 @@ impl Re {
                         self.i += consumed;
                     }
                 }
+                Quantifier::Range { min, max } => {
+                    let captures_before = self.captures.clone();
+                    let mut consumptions: Vec<usize> = Vec::new();
+                    loop {
+                        if max.is_some_and(|m| consumptions.len() >= m) {
+                            break;
+                        }
+                        if self.i >= s.len() {
+                            break;
+                        }
+                        let (is_match, consumed) =
+                            matches_string_at_index(&st, s, self.i, &mut self.captures)?;
+                        if !is_match || consumed == 0 {
+                            break;
+                        }
+                        consumptions.push(consumed);
+                        self.i += consumed;
+                    }
+
+                    if consumptions.len() < min {
+                        for n in consumptions {
+                            self.i -= n;
+                        }
+                        self.captures = captures_before;
+                        let index_before_backtracking = self.i;
+                        let could_backtrack = self.backtrack();
+                        if !could_backtrack {
+                            return Ok((false, index_before_backtracking));
+                        }
+                        continue;
+                    }
+
+                    // The first `min` repetitions are mandatory and can't be given
+                    // back; only repetitions beyond that floor are backtrackable.
+                    // Either way, a full unwind still needs the *real* amounts
+                    // each mandatory repetition consumed, not a placeholder.
+                    self.backtrack_stack.push(BacktrackState {
+                        is_backtrackable: consumptions.len() > min,
+                        matcher: self.current_state.clone().unwrap(),
+                        consumptions,
+                        captures_before,
+                    });
+                    self.current_state = self.matcher_stack.pop();
+                }
             }
         }
         Ok((true, self.i))
@@ -168,7 +380,9 @@ impl Re {
 
 pub fn test_re(re: &str, s: &str) -> Result<Option<usize>, String> {
     let chars: Vec<char> = s.chars().collect();
-    let match_result = Re::new(&parse_re(re)?).test_internal(&chars)?;
+    let matchers = parse_re(re)?;
+    let group_count = count_groups(&matchers);
+    let match_result = Re::new(&matchers, group_count + 1).test_internal(&chars)?;
     if let (true, i) = match_result {
         Ok(Some(i))
     } else {
@@ -176,6 +390,19 @@ pub fn test_re(re: &str, s: &str) -> Result<Option<usize>, String> {
     }
 }
 
+pub fn captures(re: &str, s: &str) -> Result<Option<Captures>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let matchers = parse_re(re)?;
+    let group_count = count_groups(&matchers);
+    let mut engine = Re::new(&matchers, group_count + 1);
+    let (is_match, end) = engine.test_internal(&chars)?;
+    if !is_match {
+        return Ok(None);
+    }
+    engine.captures[0] = Some((0, end));
+    Ok(Some(engine.captures))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -218,4 +445,110 @@ mod tests {
         assert_eq!(Ok(Some(3)), test_re("a.*c", "abc"));
         assert_eq!(Ok(Some(3)), test_re("abc*c", "abc"));
     }
+
+    #[test]
+    fn test_alternation() {
+        assert_eq!(Ok(Some(3)), test_re("foo|bar", "foo"));
+        assert_eq!(Ok(Some(3)), test_re("foo|bar", "bar"));
+        assert_eq!(Ok(None), test_re("foo|bar", "baz"));
+        assert_eq!(Ok(Some(3)), test_re("a(b|cd)e", "abe"));
+        assert_eq!(Ok(Some(4)), test_re("a(b|cd)e", "acde"));
+    }
+
+    #[test]
+    fn test_bracket_class() {
+        assert_eq!(Ok(Some(1)), test_re("[abc]", "b"));
+        assert_eq!(Ok(None), test_re("[abc]", "d"));
+        assert_eq!(Ok(Some(1)), test_re("[a-z0-9]", "7"));
+        assert_eq!(Ok(Some(1)), test_re("[^a-c]", "d"));
+        assert_eq!(Ok(None), test_re("[^a-c]", "b"));
+        assert_eq!(Ok(Some(1)), test_re("[[:alpha:]]", "x"));
+        assert_eq!(Ok(None), test_re("[[:alpha:]]", "3"));
+        assert_eq!(Ok(Some(3)), test_re("[a-z]+", "abc"));
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(Ok(Some(3)), test_re("a.*c$", "abc"));
+        assert_eq!(Ok(None), test_re("a.*c$", "abcd"));
+        assert_eq!(Ok(Some(1)), test_re("^a", "abc"));
+    }
+
+    #[test]
+    fn test_repetition_range() {
+        assert_eq!(Ok(None), test_re("a{2}", "a"));
+        assert_eq!(Ok(Some(2)), test_re("a{2}", "aa"));
+        assert_eq!(Ok(Some(2)), test_re("a{2}", "aaa"));
+        assert_eq!(Ok(Some(4)), test_re("a{2,}", "aaaa"));
+        assert_eq!(Ok(None), test_re("a{2,}", "a"));
+        assert_eq!(Ok(Some(3)), test_re("a{2,3}", "aaaa"));
+        assert_eq!(Ok(Some(2)), test_re("a{2,3}", "aa"));
+        // Greedy a{2,4} must give repetitions back (down to, but not below, the
+        // floor of 2) for the rest of the pattern to find a match.
+        assert_eq!(Ok(Some(5)), test_re("a{2,4}aaa", "aaaaa"));
+        assert_eq!(Ok(None), test_re("a{2,4}aaaa", "aaaaa"));
+        // When `a{2}` consumes exactly its mandatory minimum and something
+        // later fails, a full unwind needs the real per-repetition amounts to
+        // restore `self.i`, not a placeholder, or an earlier alternation can
+        // be rolled back to the wrong index.
+        assert_eq!(Ok(Some(7)), test_re("(aa|aaaa)a{2}c", "aaaaaac"));
+    }
+
+    #[test]
+    fn test_alternation_backtracks_into_next_branch() {
+        // The first branch ("a") matches greedily, but only the second
+        // branch ("ab") leaves the right remainder for the rest of the
+        // pattern to succeed against.
+        assert_eq!(Ok(Some(3)), test_re("(a|ab)c", "abc"));
+    }
+
+    #[test]
+    fn test_alternation_exhausts_all_branches_before_giving_up() {
+        // Neither "a"+[ab] nor "ab"+[ab] can match "ac"; backtracking into
+        // the second branch must actually retry the alternation, not just
+        // retry whatever comes after it at the rolled-back index.
+        assert_eq!(Ok(None), test_re("(a|ab)[ab]", "ac"));
+    }
+
+    #[test]
+    fn test_zero_or_more_fully_drained_falls_back_to_earlier_alternation() {
+        // Once ".*" has given back every repetition it took, there's
+        // nothing left for it to retry itself; backtracking must move past
+        // it to the still-untried "xy" branch instead of re-trying ".*" in
+        // place, which would repeat the same failure forever.
+        assert_eq!(Ok(None), test_re("(a|xy).*c", "axb"));
+    }
+
+    #[test]
+    fn test_captures() {
+        assert_eq!(
+            Ok(Some(vec![Some((0, 3)), Some((1, 2))])),
+            captures("a(b)c", "abc")
+        );
+        // The group is skipped entirely via the `?` branch, so it never
+        // participated in the match and reports `None`.
+        assert_eq!(
+            Ok(Some(vec![Some((0, 2)), None])),
+            captures("a(bcd)?c", "ac")
+        );
+        // The alternation's first branch matches greedily, is then
+        // backtracked into the second branch, and the capture must reflect
+        // the branch that was actually accepted, not the one tried first.
+        assert_eq!(
+            Ok(Some(vec![Some((0, 3)), Some((0, 2))])),
+            captures("(a|ab)c", "abc")
+        );
+        // Nested groups get their own slots, numbered by the order their
+        // opening paren appears.
+        assert_eq!(
+            Ok(Some(vec![Some((0, 3)), Some((0, 3)), Some((1, 3))])),
+            captures("(a(cd))", "acd")
+        );
+        assert_eq!(Ok(None), captures("abc", "xyz"));
+        // The outer `*` takes zero repetitions, so the group inside never
+        // actually ran and must report `None`, not the span of the
+        // zero-width attempt that was made (and rejected) to check that
+        // zero repetitions was in fact the right call.
+        assert_eq!(Ok(Some(vec![Some((0, 0)), None])), captures("(a*)*", "b"));
+    }
 }